@@ -0,0 +1,57 @@
+# frozen_string_literal: true
+
+require_relative 'configuration'
+require_relative 'lib/digest_builder'
+require_relative 'lib/post'
+require_relative 'lib/post_fetcher'
+require_relative 'lib/storage_adapter'
+require_relative 'lib/strategy_factory'
+
+# Usage: ruby backfill_ids.rb <hn id> [<hn id> ...]
+#
+# Adds the given stories to today's snapshot and rebuilds today's digests
+# for strategies with subscribers, except digests that were already sent.
+# Does not send any mail.
+ids = ARGV
+if ids.empty? || ids.any? { |id| id !~ /\A\d+\z/ }
+  abort 'Usage: ruby backfill_ids.rb <hn id> [<hn id> ...]'
+end
+
+current_time = Time.now
+date = Time.gm(
+  current_time.year,
+  current_time.month,
+  current_time.day,
+  Configuration::SNAPSHOT_DAILY_HOUR
+)
+
+storage_adapter = StorageAdapter.new
+fetched = PostFetcher.new.fetch_by_ids(ids).select do |_id, post|
+  Post.tags(post).include?('story')
+end
+puts "Fetched #{fetched.size} of #{ids.size} stories"
+abort 'Nothing to backfill' if fetched.empty?
+
+posts = (storage_adapter.fetch_post_snapshot(date: date) || {}).merge(fetched)
+storage_adapter.snapshot_posts(posts: posts, date: date)
+
+digest_builder = DigestBuilder.new(
+  storage_adapter: storage_adapter,
+  max_posts_per_domain: Configuration::MAX_POSTS_PER_DOMAIN,
+  blocked_keywords: Configuration::DIGEST_BLOCKED_KEYWORDS,
+  lookback_days: Configuration::DIGEST_LOOKBACK_DAYS
+)
+active_types = storage_adapter.fetch_active_subscription_types
+strategies = StrategyFactory.from_types(active_types).select do |strategy|
+  StrategyFactory.runs_on?(strategy, date)
+end
+digest_builder.unsent_strategies(
+  digest_strategies: strategies,
+  date: date
+).each do |strategy|
+  digest_builder.build_digest(
+    digest_strategy: strategy,
+    date: date,
+    posts: posts.values
+  )
+end
@@ -0,0 +1,38 @@
+# frozen_string_literal: true
+
+require_relative 'configuration'
+require_relative 'lib/post_snapshotter'
+require_relative 'lib/storage_adapter'
+require_relative 'lib/strategy_factory'
+
+# Usage: ruby backfill_snapshots.rb <from YYYY-MM-DD> <to YYYY-MM-DD>
+#
+# Snapshots every day in the range, inclusive, that doesn't have one yet.
+# Does not build digests or send any mail.
+USAGE = 'Usage: ruby backfill_snapshots.rb <from YYYY-MM-DD> <to YYYY-MM-DD>'
+
+def parse_date(arg)
+  abort USAGE unless arg =~ /\A\d{4}-\d{2}-\d{2}\z/
+
+  year, month, day = arg.split('-').map { |part| Integer(part, 10) }
+  Time.gm(year, month, day, Configuration::SNAPSHOT_DAILY_HOUR)
+end
+
+abort USAGE unless ARGV.size == 2
+
+from_date, to_date = ARGV.map { |arg| parse_date(arg) }
+abort 'The from date must not be after the to date' if from_date > to_date
+
+storage_adapter = StorageAdapter.new
+strategies = StrategyFactory.from_types(
+  storage_adapter.fetch_active_subscription_types
+)
+snapshotter = PostSnapshotter.new(storage_adapter: storage_adapter)
+snapshots = snapshotter.snapshot_range(
+  from_date: from_date,
+  to_date: to_date,
+  strategies: strategies
+)
+snapshots.each do |date, posts|
+  puts "Snapshotted #{posts.size} posts for #{date.strftime('%F')}"
+end
@@ -1,6 +1,154 @@
 # frozen_string_literal: true
 
+require_relative 'lib/ab_test'
+require_relative 'lib/errors'
+
+# All environment variables are read here, once, when this file is loaded.
 module Configuration
-  TOP_N_VALUES = [10, 20, 50].freeze
-  POINT_THRESHOLD_VALUES = [500, 250, 100].freeze
+  # Raised at load time for a missing or invalid environment variable.
+  class ConfigError < ArgumentError
+    include HNDigestError
+  end
+
+  # Parses a comma-separated list of positive, ascending integers from the
+  # environment, e.g. TOP_N_VALUES="5,10,25,50". Raises at load time so a bad
+  # value fails the Lambda on startup rather than mid-run.
+  def self.int_list_from_env(name, default)
+    raw = ENV[name]
+    return default if raw.nil? || raw.strip.empty?
+
+    values = raw.split(',', -1).map do |value|
+      Integer(value.strip, 10)
+    rescue ArgumentError
+      raise ConfigError,
+            "#{name} must be a comma-separated list of integers, " \
+            "got #{raw.inspect}"
+    end
+
+    unless values.all?(&:positive?)
+      raise ConfigError, "#{name} values must be > 0, got #{raw.inspect}"
+    end
+    unless values.each_cons(2).all? { |a, b| a < b }
+      raise ConfigError,
+            "#{name} values must be sorted ascending, got #{raw.inspect}"
+    end
+
+    values
+  end
+  private_class_method :int_list_from_env
+
+  def self.positive_int_from_env(name, default)
+    raw = ENV[name]
+    return default if raw.nil? || raw.strip.empty?
+
+    value = Integer(raw.strip, 10)
+    raise ArgumentError unless value.positive?
+
+    value
+  rescue ArgumentError
+    raise ConfigError,
+          "#{name} must be a positive integer, got #{raw.inspect}"
+  end
+  private_class_method :positive_int_from_env
+
+  # strftime format for the date in digest subjects. Checked against a sample
+  # date so a format that renders to nothing fails at startup.
+  def self.date_format_from_env(name, default)
+    format = ENV.fetch(name, default)
+    if Time.gm(2020, 5, 2).strftime(format).strip.empty?
+      raise ConfigError,
+            "#{name} must produce a non-empty date, got #{format.inspect}"
+    end
+
+    format
+  end
+  private_class_method :date_format_from_env
+
+  def self.hour_from_env(name, default)
+    raw = ENV[name]
+    return default if raw.nil? || raw.strip.empty?
+
+    value = Integer(raw.strip, 10)
+    raise ArgumentError unless (0..23).cover?(value)
+
+    value
+  rescue ArgumentError
+    raise ConfigError,
+          "#{name} must be an hour from 0 to 23, got #{raw.inspect}"
+  end
+  private_class_method :hour_from_env
+
+  # Should match the hour the Lambda is scheduled for. Defaults to 5 AM UTC
+  # -> 10pm PDT, 9pm PST.
+  SNAPSHOT_DAILY_HOUR = hour_from_env('SNAPSHOT_DAILY_HOUR', 5)
+  # Weekly digests go out on Sunday's run (UTC), i.e. Saturday evening in the
+  # US.
+  WEEKLY_DIGEST_WDAY = 0
+
+  TOP_N_VALUES = int_list_from_env('TOP_N_VALUES', [10, 20, 50]).freeze
+  POINT_THRESHOLD_VALUES = int_list_from_env(
+    'POINT_THRESHOLD_VALUES',
+    [100, 250, 500]
+  ).freeze
+  TOP_N_BY_COMMENTS_VALUES = int_list_from_env(
+    'TOP_N_BY_COMMENTS_VALUES',
+    [10, 20, 50]
+  ).freeze
+  # Stories with at least this many comments are fetched regardless of
+  # points, so TOP_N_COMMENTS digests can see heavily discussed posts.
+  MIN_FETCH_COMMENTS = 50
+  # Each tag gets a tagged variant of every TOP_N_VALUES digest.
+  REQUIRED_TAG_VALUES = %w[ask_hn show_hn].freeze
+  # Each type gets a digest of every post of that type. These are also the
+  # Algolia tags fetched on top of the regular story queries.
+  POST_TYPE_VALUES = %i[show_hn ask_hn].freeze
+  SUBJECT_DATE_FORMAT = date_format_from_env(
+    'SUBJECT_DATE_FORMAT',
+    '%b %-d, %Y'
+  ).freeze
+  DYNAMODB_TABLE = ENV.fetch('DYNAMODB_TABLE', 'HNDigest').freeze
+  ITEM_PREFIX = ENV.fetch('ITEM_PREFIX', '').freeze
+  SES_REGION = ENV.fetch('SES_REGION', 'us-west-2').freeze
+  EMAIL_FROM = ENV.fetch('EMAIL_FROM', 'hndigest@samshadwell.com').freeze
+  EMAIL_REPLY_TO = ENV.fetch('EMAIL_REPLY_TO', 'hi@samshadwell.com').freeze
+  # Unset means no cap.
+  MAX_POSTS_PER_DOMAIN = positive_int_from_env('MAX_POSTS_PER_DOMAIN', nil)
+  # Comma-separated, e.g. "blockchain,crypto". Matched case-insensitively
+  # against post titles.
+  DIGEST_BLOCKED_KEYWORDS = ENV.fetch('DIGEST_BLOCKED_KEYWORDS', '')
+                               .split(',')
+                               .map(&:strip)
+                               .reject(&:empty?)
+                               .freeze
+  # Daily digests skip posts sent in the past this many days.
+  DIGEST_LOOKBACK_DAYS = positive_int_from_env('DIGEST_LOOKBACK_DAYS', 1)
+  # Log digests instead of sending them. Everything else, including saving
+  # snapshots and digests, runs as normal.
+  DRY_RUN = ENV['DRY_RUN'] == 'true'
+  BUILD_DIGEST_TIMEOUT_SECS = positive_int_from_env(
+    'BUILD_DIGEST_TIMEOUT_SECS',
+    30
+  )
+  # Algolia's free tier allows 10 requests per second.
+  ALGOLIA_REQUESTS_PER_SECOND = positive_int_from_env(
+    'ALGOLIA_REQUESTS_PER_SECOND',
+    10
+  )
+  # How long snapshots and digests are kept in DynamoDB before they expire.
+  DYNAMO_TTL_DAYS = positive_int_from_env('DYNAMO_TTL_DAYS', 30)
+  if DYNAMO_TTL_DAYS > 3650
+    raise ConfigError,
+          "DYNAMO_TTL_DAYS must be at most 3650, got #{DYNAMO_TTL_DAYS}"
+  end
+  # SES's maximum send rate for the account, in recipients per second.
+  SES_SENDING_RATE = positive_int_from_env('SES_SENDING_RATE', 14)
+  # Optional subject-line A/B test, e.g.
+  # {"id": "subject-1", "variants": ["Hacker Digest — {date}",
+  #  "Your Daily HN Roundup — {date}"], "weights": [1, 1]}
+  AB_TEST =
+    begin
+      ENV['AB_TEST_CONFIG'] && AbTest.from_json(ENV['AB_TEST_CONFIG'])
+    rescue AbTest::InvalidConfig => e
+      raise ConfigError, "AB_TEST_CONFIG is invalid: #{e.message}"
+    end
 end
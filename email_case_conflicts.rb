@@ -0,0 +1,11 @@
+# frozen_string_literal: true
+
+require_relative 'lib/storage_adapter'
+
+# Usage: ruby email_case_conflicts.rb
+#
+# Lists stored subscriber emails that differ from their normalized form,
+# e.g. mixed-case addresses, one per line, so they can be cleaned up.
+conflicts = StorageAdapter.new.fetch_email_case_conflicts
+puts conflicts
+warn "#{conflicts.size} emails differ from their normalized form"
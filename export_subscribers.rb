@@ -0,0 +1,18 @@
+# frozen_string_literal: true
+
+require_relative 'lib/storage_adapter'
+require_relative 'lib/subscriber_exporters/csv_exporter'
+require_relative 'lib/subscriber_exporters/json_exporter'
+
+# Usage: ruby export_subscribers.rb [csv|json]
+EXPORTERS = {
+  'csv' => SubscriberExporters::CsvExporter,
+  'json' => SubscriberExporters::JsonExporter
+}.freeze
+
+format = ARGV.fetch(0, 'csv')
+exporter = EXPORTERS.fetch(format) do
+  abort "Unknown export format #{format.inspect}, expected csv or json"
+end
+
+exporter.new.export(storage_adapter: StorageAdapter.new, io: $stdout)
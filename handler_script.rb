@@ -2,17 +2,25 @@
 
 require 'aws-sdk-ses'
 
+require_relative 'configuration'
 require_relative 'lib/digest_builder'
 require_relative 'lib/digest_mailer'
 require_relative 'lib/digest_renderer'
+require_relative 'lib/dry_run_mailer'
 require_relative 'lib/post_snapshotter'
 require_relative 'lib/storage_adapter'
 require_relative 'lib/strategy_factory'
+require_relative 'lib/strategies/and'
 require_relative 'lib/strategies/over_point_threshold'
+require_relative 'lib/strategies/post_type_only'
+require_relative 'lib/strategies/require_tag'
+require_relative 'lib/strategies/top_n_by_comments'
 require_relative 'lib/strategies/top_n_posts'
+require_relative 'lib/strategies/weekly'
 
-# 5 AM UTC -> 10pm PDT, 9pm PST
-SNAPSHOT_DAILY_HOUR = 5
+StrategyFactory.all_strategies.each do |strategy|
+  DigestRenderer.validate_template!(strategy: strategy)
+end
 
 def handle(*)
   current_time = Time.now
@@ -20,26 +28,89 @@ def handle(*)
     current_time.year,
     current_time.month,
     current_time.day,
-    SNAPSHOT_DAILY_HOUR
+    Configuration::SNAPSHOT_DAILY_HOUR
   )
   storage_adapter = StorageAdapter.new
+  active_types = storage_adapter.fetch_active_subscription_types
+  subscribed_strategies = StrategyFactory.from_types(active_types)
+
+  # Weekly strategies count too, since they draw on every daily snapshot.
   snapshotter = PostSnapshotter.new(storage_adapter: storage_adapter)
-  all_posts = snapshotter.snapshot(date: date).values
+  all_posts = snapshotter.snapshot(
+    date: date,
+    strategies: subscribed_strategies
+  ).values
+
+  digest_builder = DigestBuilder.new(
+    storage_adapter: storage_adapter,
+    max_posts_per_domain: Configuration::MAX_POSTS_PER_DOMAIN,
+    blocked_keywords: Configuration::DIGEST_BLOCKED_KEYWORDS,
+    lookback_days: Configuration::DIGEST_LOOKBACK_DAYS,
+    selection_timeout: Configuration::BUILD_DIGEST_TIMEOUT_SECS
+  )
+  mailer =
+    if Configuration::DRY_RUN
+      DryRunMailer.new
+    else
+      DigestMailer.new(
+        ses_client: Aws::SES::Client.new(region: Configuration::SES_REGION)
+      )
+    end
 
-  digest_builder = DigestBuilder.new(storage_adapter: storage_adapter)
-  mailer = DigestMailer.new(ses_client: Aws::SES::Client.new(region: 'us-west-2'))
+  strategies = subscribed_strategies.select do |strategy|
+    StrategyFactory.runs_on?(strategy, date)
+  end
+  # Lambda retries a failed invocation; don't resend what already went out.
+  strategies = digest_builder.unsent_strategies(
+    digest_strategies: strategies,
+    date: date
+  )
+  recent_digests = digest_builder.prefetch_recent_digests(
+    digest_strategies: strategies,
+    date: date
+  )
 
-  StrategyFactory.all_strategies.each do |strategy|
-    posts = digest_builder.build_digest(
-      digest_strategy: strategy,
-      date: date,
-      posts: all_posts
-    )
-    renderer = DigestRenderer.new(posts: posts, date: date)
+  strategies.each do |strategy|
+    posts = begin
+      digest_builder.build_digest(
+        digest_strategy: strategy,
+        date: date,
+        posts: all_posts,
+        recent_digests: recent_digests
+      )
+    rescue DigestBuilder::SelectionTimeout
+      puts "ERROR: selecting #{strategy.type} posts timed out after " \
+           "#{Configuration::BUILD_DIGEST_TIMEOUT_SECS}s, skipping it"
+      next
+    end
 
     subscribers = storage_adapter.fetch_subscribers(type: strategy.type)
     next if subscribers.nil? || subscribers.empty?
 
-    mailer.send_mail(renderer: renderer, recipients: subscribers)
+    subscribers_by_subject(subscribers).each do |subject_template, recipients|
+      renderer = DigestRenderer.new(
+        posts: posts,
+        date: date,
+        strategy: strategy,
+        subject_template: subject_template
+      )
+      mailer.send_mail(renderer: renderer, recipients: recipients)
+    end
+    next if Configuration::DRY_RUN
+
+    storage_adapter.mark_digest_sent(type: strategy.type, date: date)
+  end
+end
+
+# Groups subscribers by their A/B test subject variant. A nil key means the
+# default subject.
+def subscribers_by_subject(subscribers)
+  ab_test = Configuration::AB_TEST
+  return { nil => subscribers } if ab_test.nil?
+
+  groups = subscribers.group_by { |email| ab_test.assign(email) }
+  groups.each do |variant, group|
+    puts "A/B test #{ab_test.id}: sending #{variant.inspect} to #{group.size}"
   end
+  groups
 end
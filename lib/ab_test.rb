@@ -0,0 +1,64 @@
+# frozen_string_literal: true
+
+require 'digest'
+require 'json'
+
+require_relative 'errors'
+
+# A subject-line experiment. Each variant is a subject template where
+# "{date}" is replaced with the formatted digest date.
+class AbTest
+  class InvalidConfig < ArgumentError
+    include HNDigestError
+  end
+
+  attr_reader :id, :variants, :weights
+
+  def self.from_json(json)
+    config = JSON.parse(json)
+    unless config.is_a?(Hash)
+      raise InvalidConfig, "A/B test config must be a JSON object, got #{json}"
+    end
+
+    new(
+      id: config.fetch('id'),
+      variants: config.fetch('variants'),
+      weights: config.fetch('weights')
+    )
+  rescue JSON::ParserError, KeyError => e
+    raise InvalidConfig, "Invalid A/B test config: #{e.message}"
+  end
+
+  def initialize(id:, variants:, weights:)
+    unless variants.is_a?(Array) && variants.all?(String) &&
+           weights.is_a?(Array) && weights.all?(Numeric)
+      raise InvalidConfig,
+            "A/B test #{id} needs a list of subject variants and a list of " \
+            'numeric weights'
+    end
+    if variants.empty? || variants.size != weights.size
+      raise InvalidConfig,
+            "A/B test #{id} needs one weight per variant and at least one"
+    end
+    unless weights.all?(&:positive?)
+      raise InvalidConfig, "A/B test #{id} weights must be > 0"
+    end
+
+    @id = id
+    @variants = variants
+    @weights = weights
+  end
+
+  # Deterministic, so a subscriber sees the same variant on every run.
+  def assign(email)
+    hash = Digest::SHA256.hexdigest("#{email.downcase}##{@id}")
+    point = hash[0, 15].to_i(16).fdiv(16**15) * @weights.sum
+
+    @variants.zip(@weights).each do |variant, weight|
+      return variant if point < weight
+
+      point -= weight
+    end
+    @variants.last
+  end
+end
@@ -1,25 +1,182 @@
 # frozen_string_literal: true
 
+require 'json'
+require 'timeout'
+
+require_relative 'errors'
+require_relative 'post_filters/chain'
+require_relative 'post_filters/exclude_sent'
+require_relative 'post_filters/keyword_blocklist'
+require_relative 'post_filters/max_posts_per_domain'
+require_relative 'strategies/weekly'
+
 class DigestBuilder
   A_DAY = 24 * 60 * 60 # Seconds in a day.
   private_constant :A_DAY
 
-  def initialize(storage_adapter:)
+  WEEK_DAYS = 7
+  private_constant :WEEK_DAYS
+
+  # Raised instead of Timeout::Error so the AWS SDK's network error handling
+  # can't swallow it.
+  class SelectionTimeout < StandardError
+    include HNDigestError
+  end
+
+  DigestExplanation = Struct.new(
+    :included,
+    :excluded_by_dedup,
+    :excluded_by_keyword,
+    :excluded_by_domain_cap,
+    :excluded_by_strategy,
+    keyword_init: true
+  )
+
+  SimulatedDigest = Struct.new(
+    :posts,
+    :deduped_count,
+    :keyword_blocked_count,
+    :domain_capped_count,
+    :strategy_filtered_count,
+    keyword_init: true
+  )
+
+  # max_posts_per_domain keeps one site from taking over a digest. Self
+  # posts, which have no URL, aren't capped. nil means no cap.
+  # Posts whose title contains any of blocked_keywords, ignoring case, are
+  # left out of every digest. Daily digests skip posts sent in a digest of
+  # the same type in the past lookback_days days.
+  # build_digest raises SelectionTimeout if reading and selecting posts
+  # takes longer than selection_timeout seconds. nil means no limit.
+  def initialize(storage_adapter:, max_posts_per_domain: nil,
+                 blocked_keywords: [], lookback_days: 1,
+                 selection_timeout: nil)
     @storage = storage_adapter
+    @lookback_days = lookback_days
+    @selection_timeout = selection_timeout
+    @keyword_filter = PostFilters::KeywordBlocklist.new(blocked_keywords)
+    @domain_filter =
+      if max_posts_per_domain.nil?
+        PostFilters::Chain.new
+      else
+        PostFilters::MaxPostsPerDomain.new(max_posts_per_domain)
+      end
+    @filter_chain = PostFilters::Chain.new
+                                      .add(@keyword_filter)
+                                      .add(@domain_filter)
   end
 
-  def build_digest(digest_strategy:, date:, posts:)
-    yesterday_digest = @storage.fetch_digest(
-      type: digest_strategy.type,
-      date: date - A_DAY
+  # recent_digests is the result of prefetch_recent_digests. Without it the
+  # past lookback_days digests are read from storage for this strategy alone.
+  def build_digest(digest_strategy:, date:, posts:, recent_digests: nil)
+    build(
+      digest_strategy: digest_strategy,
+      date: date,
+      posts: posts,
+      recent_digests: recent_digests,
+      persist: true
     )
+  end
 
-    unsent_posts = remove_sent_posts(
-      all_posts: posts,
-      yesterday_digest: yesterday_digest
-    ).sort_by { |post| post['points'] }.reverse
+  # Exactly what build_digest would select and return, but the digest is not
+  # saved, so running it doesn't change what later digests dedupe against.
+  def build_digest_dry_run(digest_strategy:, date:, posts:,
+                           recent_digests: nil)
+    build(
+      digest_strategy: digest_strategy,
+      date: date,
+      posts: posts,
+      recent_digests: recent_digests,
+      persist: false
+    )
+  end
+
+  # Reads the past lookback_days digests for every strategy, one batch per
+  # day, as { type => [digest, ...] }. Days with no digest are left out.
+  def prefetch_recent_digests(digest_strategies:, date:)
+    types = digest_strategies.map(&:type)
+    recent_digests = {}
+    (1..@lookback_days).each do |days_ago|
+      @storage.fetch_digests(types: types, date: date - days_ago * A_DAY)
+              .each { |type, digest| (recent_digests[type] ||= []) << digest }
+    end
+
+    recent_digests
+  end
+
+  # Drops strategies whose digest for date has already been sent. Rebuilding
+  # one would overwrite the record of what went out, which later digests
+  # dedupe against.
+  def unsent_strategies(digest_strategies:, date:)
+    todays_digests = @storage.fetch_digests(
+      types: digest_strategies.map(&:type),
+      date: date
+    )
+
+    digest_strategies.reject do |strategy|
+      sent = todays_digests.dig(strategy.type, 'sent_at')
+      puts "#{strategy.type} digest already sent, skipping it" if sent
+      sent
+    end
+  end
+
+  # Runs the same selection as build_digest without saving anything, and
+  # reports which step each post was dropped at. For weekly strategies the
+  # candidates are the whole week's posts, as in build_digest.
+  def explain_selection(digest_strategy:, date:, posts:)
+    candidates, unsent_posts = candidate_posts(
+      digest_strategy: digest_strategy,
+      date: date,
+      posts: posts
+    )
+    allowed_posts = @keyword_filter.apply(unsent_posts)
+    capped_posts = @domain_filter.apply(allowed_posts)
+    included = digest_strategy.select(capped_posts)
+
+    DigestExplanation.new(
+      included: included,
+      excluded_by_dedup: posts_not_in(candidates, unsent_posts),
+      excluded_by_keyword: posts_not_in(unsent_posts, allowed_posts),
+      excluded_by_domain_cap: posts_not_in(allowed_posts, capped_posts),
+      excluded_by_strategy: posts_not_in(capped_posts, included)
+    )
+  end
+
+  # What build_digest would select for the given posts, e.g. historical
+  # snapshots, without saving anything.
+  def simulate(digest_strategy:, date:, posts:)
+    explanation = explain_selection(
+      digest_strategy: digest_strategy,
+      date: date,
+      posts: posts
+    )
+
+    SimulatedDigest.new(
+      posts: explanation.included,
+      deduped_count: explanation.excluded_by_dedup.size,
+      keyword_blocked_count: explanation.excluded_by_keyword.size,
+      domain_capped_count: explanation.excluded_by_domain_cap.size,
+      strategy_filtered_count: explanation.excluded_by_strategy.size
+    )
+  end
+
+  def remove_sent_posts(all_posts:, sent_post_ids:)
+    PostFilters::ExcludeSent.new(sent_post_ids).apply(all_posts)
+  end
 
-    selected_posts = digest_strategy.select(unsent_posts)
+  private
+
+  def build(digest_strategy:, date:, posts:, recent_digests:, persist:)
+    candidates, unsent_posts, filtered_posts, selected_posts =
+      with_selection_timeout do
+        select_posts(
+          digest_strategy: digest_strategy,
+          date: date,
+          posts: posts,
+          recent_digests: recent_digests
+        )
+      end
+    return selected_posts unless persist
 
     @storage.save_digest(
       type: digest_strategy.type,
@@ -27,16 +184,150 @@ class DigestBuilder
       posts: selected_posts
     )
 
+    log_quality_metric(
+      digest_strategy: digest_strategy,
+      date: date,
+      total_snapshotted: candidates.size,
+      after_dedup: unsent_posts.size,
+      after_filters: filtered_posts.size,
+      after_strategy: selected_posts.size
+    )
+
     selected_posts
   end
 
-  def remove_sent_posts(all_posts:, yesterday_digest:)
-    return all_posts if yesterday_digest.nil?
+  # Only reading and selecting posts is timed. The digest is saved after,
+  # so a timeout can't leave it saved but never sent.
+  def with_selection_timeout(&block)
+    return yield if @selection_timeout.nil?
+
+    Timeout.timeout(@selection_timeout, SelectionTimeout, &block)
+  end
+
+  # The candidate posts, those not already sent, those left after filtering
+  # and those the strategy selects.
+  def select_posts(digest_strategy:, date:, posts:, recent_digests:)
+    candidates, unsent_posts = candidate_posts(
+      digest_strategy: digest_strategy,
+      date: date,
+      posts: posts,
+      recent_digests: recent_digests
+    )
+    filtered_posts = filter_posts(unsent_posts)
+    selected_posts = digest_strategy.select(filtered_posts)
+
+    [candidates, unsent_posts, filtered_posts, selected_posts]
+  end
+
+  # The posts a digest draws on, and those of them not already sent, best
+  # first. posts is today's snapshot; weekly strategies add the rest of the
+  # week's.
+  def candidate_posts(digest_strategy:, date:, posts:, recent_digests: nil)
+    if digest_strategy.is_a?(Strategies::Weekly)
+      return weekly_candidate_posts(
+        digest_strategy: digest_strategy,
+        date: date,
+        posts: posts
+      )
+    end
+
+    unsent_posts = fetch_unsent_posts(
+      digest_strategy: digest_strategy,
+      date: date,
+      posts: posts,
+      recent_digests: recent_digests
+    )
+    [posts, unsent_posts]
+  end
+
+  # The previous six days' snapshots are added to posts, and anything sent
+  # in a digest of this type in the past week is dropped.
+  def weekly_candidate_posts(digest_strategy:, date:, posts:)
+    week_posts = fetch_week_of_posts(date: date, todays_posts: posts)
+
+    sent_post_ids = (1..WEEK_DAYS).flat_map do |days_ago|
+      digest = @storage.fetch_digest(
+        type: digest_strategy.type,
+        date: date - days_ago * A_DAY
+      )
+      (digest && digest['posts']) || []
+    end.map { |post| post['objectID'] }
+
+    unsent_posts = PostFilters::ExcludeSent.new(sent_post_ids)
+                                           .apply(week_posts)
+                                           .sort_by { |post| post['points'] }
+                                           .reverse
+
+    [week_posts, unsent_posts]
+  end
+
+  # Expects posts sorted best first; see PostFilters::MaxPostsPerDomain.
+  def filter_posts(posts)
+    @filter_chain.apply(posts)
+  end
+
+  # Later snapshots win so each post keeps its most recent points.
+  def fetch_week_of_posts(date:, todays_posts:)
+    posts_by_id = {}
+    (WEEK_DAYS - 1).downto(1) do |days_ago|
+      snapshot = @storage.fetch_post_snapshot(date: date - days_ago * A_DAY)
+      posts_by_id.merge!(snapshot) unless snapshot.nil?
+    end
+    todays_posts.each { |post| posts_by_id[post['objectID']] = post }
+
+    posts_by_id.values
+  end
+
+  def fetch_unsent_posts(digest_strategy:, date:, posts:, recent_digests: nil)
+    digests =
+      if recent_digests.nil?
+        (1..@lookback_days).filter_map do |days_ago|
+          @storage.fetch_digest(
+            type: digest_strategy.type,
+            date: date - days_ago * A_DAY
+          )
+        end
+      else
+        recent_digests.fetch(digest_strategy.type, [])
+      end
 
-    yesterday_posts = yesterday_digest['posts']
-    return all_posts if yesterday_posts.nil?
+    remove_sent_posts(
+      all_posts: posts,
+      sent_post_ids: sent_post_ids(digests)
+    ).sort_by { |post| post['points'] }.reverse
+  end
+
+  def sent_post_ids(digests)
+    digests.flat_map { |digest| digest['posts'] || [] }
+           .map { |post| post['objectID'] }
+  end
+
+  # Logged as a single JSON line so a CloudWatch metric filter can extract
+  # fields such as after_strategy. after_filters is what's left once the
+  # keyword blocklist and domain cap have run on the deduped posts.
+  def log_quality_metric(digest_strategy:, date:, total_snapshotted:,
+                         after_dedup:, after_filters:, after_strategy:)
+    dedup_ratio =
+      if total_snapshotted.zero?
+        0.0
+      else
+        1.0 - after_dedup.fdiv(total_snapshotted)
+      end
+
+    puts JSON.generate(
+      metric: 'digest_quality',
+      date: date.getutc.strftime('%F'),
+      strategy: digest_strategy.type,
+      total_snapshotted: total_snapshotted,
+      after_dedup: after_dedup,
+      after_filters: after_filters,
+      after_strategy: after_strategy,
+      dedup_ratio: dedup_ratio
+    )
+  end
 
-    sent_post_ids = yesterday_posts.map { |post| post['objectID'] }.to_set
-    all_posts.reject { |post| sent_post_ids.include?(post['objectID']) }
+  def posts_not_in(posts, kept_posts)
+    kept_ids = kept_posts.map { |post| post['objectID'] }.to_set
+    posts.reject { |post| kept_ids.include?(post['objectID']) }
   end
 end
@@ -2,33 +2,57 @@
 
 require 'aws-sdk-ses'
 
+require_relative '../configuration'
+
 class DigestMailer
-  SES_RECIPIENT_LIMIT = 50
-  private_constant :SES_RECIPIENT_LIMIT
+  # max_attempts includes the first try. Retries wait base_delay seconds,
+  # then twice that, and so on.
+  RetryConfig = Struct.new(:max_attempts, :base_delay, keyword_init: true)
+
+  DEFAULT_RETRY_CONFIG = RetryConfig.new(max_attempts: 4, base_delay: 0.1)
 
-  FROM = 'hndigest@samshadwell.com'
-  private_constant :FROM
+  # Throttling and SES-side failures. Errors such as MessageRejected or
+  # AccountSendingPausedException won't go away on retry.
+  RETRYABLE_ERROR_CODES = %w[
+    Throttling
+    ThrottlingException
+    ServiceUnavailable
+    InternalFailure
+  ].freeze
+  private_constant :RETRYABLE_ERROR_CODES
 
-  REPLY_TO = 'hi@samshadwell.com'
-  private_constant :REPLY_TO
+  SES_RECIPIENT_LIMIT = 50
+  private_constant :SES_RECIPIENT_LIMIT
 
   ENCODING = 'UTF-8'
   private_constant :ENCODING
 
-  def initialize(ses_client:)
+  # SES counts every recipient of a message against the send rate, so sends
+  # are spaced out, across calls too, to stay under sending_rate recipients
+  # per second.
+  def initialize(ses_client:, from: Configuration::EMAIL_FROM,
+                 reply_to: Configuration::EMAIL_REPLY_TO,
+                 sending_rate: Configuration::SES_SENDING_RATE,
+                 retry_config: DEFAULT_RETRY_CONFIG)
     @ses_client = ses_client
+    @from = from
+    @reply_to = reply_to
+    @sending_rate = sending_rate
+    @next_send_at = 0.0
+    @retry_config = retry_config
   end
 
   def send_mail(renderer:, recipients:)
     recipients.each_slice(SES_RECIPIENT_LIMIT) do |recipients_slice|
-      puts 'Sending mail via SES...'
-      response = @ses_client.send_email({
-        source: FROM,
+      wait_for_send_rate(recipients_slice.size)
+      puts "Sending mail via SES to #{recipients_slice.size} recipients..."
+      response = send_with_retries({
+        source: @from,
         destination: {
           bcc_addresses: recipients_slice
         },
-        reply_to_addresses: [REPLY_TO],
-        return_path: REPLY_TO,
+        reply_to_addresses: [@reply_to],
+        return_path: @reply_to,
         message: {
           subject: {
             data: renderer.subject,
@@ -45,4 +69,34 @@ class DigestMailer
       puts "Success! message_id=#{response.message_id}"
     end
   end
+
+  private
+
+  def send_with_retries(params)
+    attempt = 1
+    begin
+      @ses_client.send_email(params)
+    rescue Aws::SES::Errors::ServiceError => e
+      raise if !retryable?(e) || attempt >= @retry_config.max_attempts
+
+      delay = @retry_config.base_delay * 2**(attempt - 1)
+      puts "SES send failed with #{e.code}, attempt #{attempt}, " \
+           "retrying in #{delay}s"
+      sleep(delay)
+      attempt += 1
+      retry
+    end
+  end
+
+  def retryable?(error)
+    RETRYABLE_ERROR_CODES.include?(error.code) ||
+      error.context.http_response.status_code >= 500
+  end
+
+  def wait_for_send_rate(recipient_count)
+    now = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+    sleep(@next_send_at - now) if @next_send_at > now
+    @next_send_at = [now, @next_send_at].max +
+                    recipient_count.fdiv(@sending_rate)
+  end
 end
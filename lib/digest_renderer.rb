@@ -2,21 +2,40 @@
 
 require 'erb'
 
+require_relative 'errors'
+require_relative 'post'
+require_relative 'strategies/weekly'
+require_relative '../configuration'
+
 class DigestRenderer
+  include ERB::Util
+
+  class TemplateError < StandardError
+    include HNDigestError
+  end
+
   TEMPLATE = %(
-    Your daily Hacker News digest:
+    Your <%= cadence %> Hacker News digest (<%= h(@strategy.description) %>):
     <br>
     <% for @post in @posts %>
       <p>
         <%= @post['title'] %>
         <br>
         <%= @post['points'] %> points -
+        <% if @post['author'] %>
+          by <%= h(@post['author']) %> -
+        <% end %>
+        <% reading_time = Post.reading_time_minutes(@post) %>
+        <% if reading_time&.positive? %>
+          ~<%= reading_time.ceil %> min read -
+        <% end %>
         <% if @post['url'] %>
           <a href="<%= @post['url'] %>">
             link
           </a> -
         <% end %>
         <a href="https://news.ycombinator.com/item?id=<%= @post['objectID'] %>">
+          <%= @post['num_comments'] || 0 %>
           comments
         </a>
       </p>
@@ -26,16 +45,64 @@ class DigestRenderer
   )
   private_constant :TEMPLATE
 
-  def initialize(posts:, date:)
+  DEFAULT_SUBJECT = 'Hacker News Digest for {date}'
+  private_constant :DEFAULT_SUBJECT
+
+  SAMPLE_POSTS = [
+    {
+      'objectID' => '1',
+      'title' => 'Sample link post',
+      'url' => 'https://example.com',
+      'points' => 100,
+      'num_comments' => 20,
+      'author' => 'pg',
+      'created_at' => '2020-05-02T05:00:00.000Z',
+      'tags' => ['story']
+    },
+    {
+      'objectID' => '2',
+      'title' => 'Ask HN: Sample self post',
+      'word_count' => 300,
+      'points' => 50,
+      'num_comments' => 10,
+      'author' => 'dang',
+      'created_at' => '2020-05-02T05:00:00.000Z',
+      'tags' => %w[story ask_hn]
+    }
+  ].freeze
+  private_constant :SAMPLE_POSTS
+
+  # Renders placeholder posts so a broken template fails the Lambda at init
+  # rather than when the first digest is sent.
+  def self.validate_template!(strategy:)
+    renderer = new(posts: SAMPLE_POSTS, date: Time.now, strategy: strategy)
+    renderer.subject
+    renderer.content
+  rescue StandardError => e
+    raise TemplateError,
+          "Digest template failed to render for #{strategy.type}: " \
+          "#{e.class}: #{e.message}"
+  end
+
+  def initialize(posts:, date:, strategy:, subject_template: nil)
     @date = date
     @posts = posts
+    @strategy = strategy
+    @subject_template = subject_template || DEFAULT_SUBJECT
   end
 
   def subject
-    "Hacker News Digest for #{@date.getutc.strftime('%b %-d, %Y')}"
+    formatted_date = @date.getutc.strftime(Configuration::SUBJECT_DATE_FORMAT)
+    @subject_template.gsub('{date}', formatted_date)
   end
 
   def content
     ERB.new(TEMPLATE, trim_mode: '>-').result(binding)
   end
+
+  private
+
+  def cadence
+    @strategy.is_a?(Strategies::Weekly) ? 'weekly' : 'daily'
+  end
 end
@@ -0,0 +1,18 @@
+# frozen_string_literal: true
+
+# Stands in for DigestMailer when DRY_RUN=true. Logs what would be sent
+# instead of calling SES.
+class DryRunMailer
+  PREVIEW_LENGTH = 100
+  private_constant :PREVIEW_LENGTH
+
+  def send_mail(renderer:, recipients:)
+    subject = renderer.subject
+    preview = renderer.content.strip[0, PREVIEW_LENGTH]
+
+    recipients.each do |recipient|
+      puts "DRY RUN: would send to #{recipient}, subject: #{subject}"
+    end
+    puts "DRY RUN: content starts with #{preview.inspect}"
+  end
+end
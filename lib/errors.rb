@@ -0,0 +1,6 @@
+# frozen_string_literal: true
+
+# Included by every error this project raises on purpose, so callers can
+# rescue them all with `rescue HNDigestError` while each class keeps its own
+# superclass, e.g. ArgumentError for invalid configuration.
+module HNDigestError; end
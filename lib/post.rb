@@ -0,0 +1,56 @@
+# frozen_string_literal: true
+
+require 'uri'
+
+# Helpers for the post hashes returned by PostFetcher.
+module Post
+  WORDS_PER_MINUTE = 200
+  private_constant :WORDS_PER_MINUTE
+
+  # Algolia tags such as "story", "ask_hn" or "show_hn". Posts snapshotted
+  # before tags were stored have none.
+  def self.tags(post)
+    post['tags'] || []
+  end
+
+  POST_TYPES = %i[show_hn ask_hn job story].freeze
+  private_constant :POST_TYPES
+
+  # One of :show_hn, :ask_hn, :job, :story or :other, from the post's tags.
+  # Show and Ask HN posts are also tagged story, so they are checked first.
+  def self.post_type(post)
+    tags = tags(post)
+    POST_TYPES.find { |post_type| tags.include?(post_type.to_s) } || :other
+  end
+
+  # Lowercased host of a post URL without a leading "www.", e.g.
+  # "https://www.Example.com/a" -> "example.com". URLs missing a scheme are
+  # treated as http. Returns nil for self posts and unparseable URLs.
+  def self.url_domain(url)
+    return nil if url.nil? || url.strip.empty?
+
+    url = url.strip
+    url = "http://#{url}" unless url.match?(%r{\A[a-z][a-z0-9+.-]*://}i)
+    host = URI.parse(url).host
+    host&.downcase&.delete_prefix('www.')
+  rescue URI::InvalidURIError
+    nil
+  end
+
+  # Words in a self post's HTML body, or nil for link posts without one.
+  def self.word_count(story_text)
+    return nil if story_text.nil?
+
+    story_text.gsub(/<[^>]*>/, ' ').split.size
+  end
+
+  # Estimated reading time for self posts (e.g. Ask HN) based on their word
+  # count. Posts stored before word_count existed kept their story_text
+  # instead. Returns nil for link posts.
+  def self.reading_time_minutes(post)
+    words = post['word_count'] || word_count(post['story_text'])
+    return nil if words.nil?
+
+    words.fdiv(WORDS_PER_MINUTE)
+  end
+end
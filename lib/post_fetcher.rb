@@ -1,47 +1,243 @@
 # frozen_string_literal: true
 
+require 'cgi'
 require 'http'
 require 'json'
 
+require_relative 'errors'
+require_relative 'post'
+require_relative 'rate_limiter'
+require_relative '../configuration'
+
 class PostFetcher
+  class FetchError < StandardError
+    include HNDigestError
+  end
+
+  # Raised internally for responses worth retrying.
+  class ServerError < StandardError; end
+  private_constant :ServerError
+
   HOST = 'https://hn.algolia.com'
   PATH = '/api/v1/search'
   SECONDS_IN_DAY = 60 * 60 * 24
+  HITS_PER_PAGE = 1000 # Algolia's maximum page size.
+  REQUEST_TIMEOUT = 10 # Seconds.
+  # Most by-id requests in flight at once. The rate limiter still applies.
+  FETCH_BY_IDS_CONCURRENCY = 20
+  # Longest Retry-After honored, so a 429 can't run the Lambda out of time.
+  MAX_RETRY_AFTER = 60 # Seconds.
+
+  # Connection errors, timeouts and 5xx responses are retried up to
+  # max_retries times, waiting about base_delay seconds, then twice that,
+  # and so on, with jitter. A 429 is retried once after its Retry-After, or
+  # default_retry_after seconds if it has none.
+  def initialize(
+    requests_per_second: Configuration::ALGOLIA_REQUESTS_PER_SECOND,
+    max_retries: 3,
+    base_delay: 0.5,
+    default_retry_after: 5
+  )
+    @rate_limiter = RateLimiter.new(requests_per_second: requests_per_second)
+    @max_retries = max_retries
+    @base_delay = base_delay
+    @default_retry_after = default_retry_after
+  end
+
+  # tags are extra Algolia tags, such as show_hn, to fetch every post of.
+  # Only posts created at or after since, and before before if given, are
+  # fetched.
+  def fetch(top_k:, points:, comments:, since:, tags: [], before: nil)
+    created = created_at_filter(since, before)
+    HTTP.timeout(REQUEST_TIMEOUT).persistent(HOST) do |client|
+      top_k = fetch_top_k(top_k, client: client, created: created)
+      by_points = fetch_by_points(points, client: client, created: created)
+      by_comments = fetch_by_comments(
+        comments,
+        client: client,
+        created: created
+      )
+      by_tag = tags.map do |tag|
+        fetch_by_tag(tag, client: client, created: created)
+      end
+
+      [top_k, by_points, by_comments, *by_tag].reduce(:merge)
+    end
+  end
+
+  # Fetches specific stories by HN id, e.g. to backfill a snapshot. Ids that
+  # Algolia doesn't know about, or that aren't stories (comments, poll
+  # options and so on), are left out of the result. Up to
+  # FETCH_BY_IDS_CONCURRENCY requests run at once, each worker thread with
+  # its own connection.
+  def fetch_by_ids(ids)
+    queue = Queue.new
+    ids.each { |id| queue << id }
+    queue.close
+
+    workers = Array.new([FETCH_BY_IDS_CONCURRENCY, ids.size].min) do
+      Thread.new do
+        HTTP.timeout(REQUEST_TIMEOUT).persistent(HOST) do |client|
+          posts = {}
+          while (id = queue.pop)
+            post = fetch_story(id, client: client)
+            posts[post['objectID']] = post unless post.nil?
+          end
+          posts
+        end
+      end
+    end
 
-  def self.fetch(top_k:, points:, since:)
-    HTTP.persistent(HOST) do |client|
-      top_k = fetch_top_k(top_k, client: client, since: since)
-      by_points = fetch_by_points(points, client: client, since: since)
+    workers.map(&:value).reduce({}, :merge)
+  end
 
-      top_k.merge(by_points)
+  # One story by HN id, with the same fields and tags as a snapshot post.
+  # Returns nil if Algolia doesn't know about the id or it isn't a story.
+  def fetch_single(id)
+    HTTP.timeout(REQUEST_TIMEOUT).persistent(HOST) do |client|
+      fetch_story(id, client: client)
     end
   end
 
-  def self.fetch_top_k(top_k, client:, since:)
+  private
+
+  def fetch_top_k(top_k, client:, created:)
     path = PATH + "?hitsPerPage=#{top_k}&" \
       'tags=story&' \
-      "numericFilters=created_at_i>=#{since.to_i}"
+      "numericFilters=#{created}"
 
     fetch_posts_from_path(path, client: client)
   end
-  private_class_method :fetch_top_k
 
-  def self.fetch_by_points(points, client:, since:)
-    path = PATH + '?hitsPerPage=10000&' \
+  def fetch_by_points(points, client:, created:)
+    path = PATH + "?hitsPerPage=#{HITS_PER_PAGE}&" \
       'tags=story&' \
-      "numericFilters=created_at_i>=#{since.to_i},points>=#{points}"
+      "numericFilters=#{created},points>=#{points}"
 
-    fetch_posts_from_path(path, client: client)
+    fetch_all_pages(path, client: client)
+  end
+
+  def fetch_by_comments(comments, client:, created:)
+    path = PATH + "?hitsPerPage=#{HITS_PER_PAGE}&" \
+      'tags=story&' \
+      "numericFilters=#{created},num_comments>=#{comments}"
+
+    fetch_all_pages(path, client: client)
+  end
+
+  def fetch_by_tag(tag, client:, created:)
+    path = PATH + "?hitsPerPage=#{HITS_PER_PAGE}&" \
+      "tags=#{CGI.escape(tag.to_s)}&" \
+      "numericFilters=#{created}"
+
+    fetch_all_pages(path, client: client)
+  end
+
+  def created_at_filter(since, before)
+    filter = "created_at_i>=#{since.to_i}"
+    filter += ",created_at_i<#{before.to_i}" unless before.nil?
+    filter
+  end
+
+  # Follows Algolia's page parameter until a page comes back short or the
+  # last page reported by nbPages has been fetched.
+  def fetch_all_pages(path, client:)
+    posts = {}
+    page = 0
+    loop do
+      result = fetch_result("#{path}&page=#{page}", client: client)
+      posts.merge!(posts_from_result(result))
+      page += 1
+
+      break if result['hits'].size < HITS_PER_PAGE
+      break if result['nbPages'] && page >= result['nbPages']
+    end
+
+    posts
+  end
+
+  def fetch_posts_from_path(path, client:)
+    posts_from_result(fetch_result(path, client: client))
+  end
+
+  def fetch_result(path, client:)
+    response = get_with_retries(path, client: client)
+    unless response.status.success?
+      raise FetchError, "GET #{path} failed with HTTP #{response.code}"
+    end
+
+    JSON.parse(response.to_s)
+  end
+
+  # Looks the id up through search rather than the items endpoint, so the
+  # post has the same fields and tags as the rest of the snapshot. Only
+  # stories carry both the story and story_<id> tags, so any other item
+  # comes back as nil.
+  def fetch_story(id, client:)
+    path = PATH + "?tags=story,story_#{id}"
+    fetch_posts_from_path(path, client: client)[id.to_s]
+  end
+
+  def get_with_retries(path, client:)
+    response = get_with_backoff(path, client: client)
+    return response unless response.code == 429
+
+    response.flush
+    wait = retry_after(response)
+    puts "GET #{path} was rate limited, retrying in #{wait}s"
+    sleep(wait)
+    get_with_backoff(path, client: client)
+  end
+
+  def get_with_backoff(path, client:)
+    retries = 0
+    begin
+      @rate_limiter.acquire
+      response = client.get(path)
+      if response.status.server_error?
+        response.flush
+        raise ServerError, "HTTP #{response.code}"
+      end
+
+      response
+    rescue HTTP::Error, ServerError => e
+      if retries >= @max_retries
+        raise FetchError,
+              "GET #{path} failed after #{retries} retries: #{e.message}"
+      end
+
+      retries += 1
+      delay = @base_delay * 2**(retries - 1)
+      puts "GET #{path} failed (#{e.message}), retry #{retries} in " \
+           "#{delay.round(2)}s"
+      sleep(delay * rand(0.5..1.0))
+      retry
+    end
+  end
+
+  # Retry-After in seconds, capped at MAX_RETRY_AFTER. HTTP dates and other
+  # values fall back to default_retry_after.
+  def retry_after(response)
+    seconds = Integer(response.headers['Retry-After'].to_s.strip, 10)
+    seconds.clamp(0, MAX_RETRY_AFTER)
+  rescue ArgumentError
+    [@default_retry_after, MAX_RETRY_AFTER].min
   end
-  private_class_method :fetch_by_points
 
-  def self.fetch_posts_from_path(path, client:)
-    result = JSON.parse(client.get(path).to_s)
+  # Self post bodies are reduced to a word count, which is all the digest
+  # needs, to keep snapshot chunks and digests well under DynamoDB's item
+  # size limit.
+  def posts_from_result(result)
     posts = result['hits'].map do |full_p|
-      full_p.slice('created_at', 'title', 'url', 'points', 'objectID')
+      full_p.slice(
+        'created_at', 'title', 'url', 'points', 'num_comments', 'objectID',
+        'author'
+      ).merge(
+        'word_count' => Post.word_count(full_p['story_text']),
+        'tags' => full_p['_tags'] || []
+      )
     end
 
     posts.map { |p| [p['objectID'], p] }.to_h
   end
-  private_class_method :fetch_posts_from_path
 end
@@ -0,0 +1,20 @@
+# frozen_string_literal: true
+
+module PostFilters
+  # Applies filters in the order they were added, e.g.
+  #   Chain.new.add(KeywordBlocklist.new(%w[crypto])).add(ExcludeSent.new(ids))
+  class Chain
+    def initialize(filters = [])
+      @filters = filters.dup
+    end
+
+    def add(filter)
+      @filters << filter
+      self
+    end
+
+    def apply(posts)
+      @filters.reduce(posts) { |filtered, filter| filter.apply(filtered) }
+    end
+  end
+end
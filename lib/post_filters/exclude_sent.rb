@@ -0,0 +1,16 @@
+# frozen_string_literal: true
+
+require 'set'
+
+module PostFilters
+  # Drops posts whose objectID is in sent_post_ids.
+  class ExcludeSent
+    def initialize(sent_post_ids)
+      @sent_post_ids = sent_post_ids.to_set
+    end
+
+    def apply(posts)
+      posts.reject { |post| @sent_post_ids.include?(post['objectID']) }
+    end
+  end
+end
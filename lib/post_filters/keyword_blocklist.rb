@@ -0,0 +1,19 @@
+# frozen_string_literal: true
+
+module PostFilters
+  # Drops posts whose title contains any of keywords, ignoring case.
+  class KeywordBlocklist
+    def initialize(keywords)
+      @keywords = keywords.map(&:downcase)
+    end
+
+    def apply(posts)
+      return posts if @keywords.empty?
+
+      posts.reject do |post|
+        title = (post['title'] || '').downcase
+        @keywords.any? { |keyword| title.include?(keyword) }
+      end
+    end
+  end
+end
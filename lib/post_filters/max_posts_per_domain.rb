@@ -0,0 +1,22 @@
+# frozen_string_literal: true
+
+require_relative '../post'
+
+module PostFilters
+  # Keeps at most max_posts posts from any one domain. Self posts, which have
+  # no URL, aren't capped. Expects posts sorted best first, so the best posts
+  # from each domain are the ones kept.
+  class MaxPostsPerDomain
+    def initialize(max_posts)
+      @max_posts = max_posts
+    end
+
+    def apply(posts)
+      counts = Hash.new(0)
+      posts.select do |post|
+        domain = Post.url_domain(post['url'])
+        domain.nil? || (counts[domain] += 1) <= @max_posts
+      end
+    end
+  end
+end
@@ -1,24 +1,63 @@
 # frozen_string_literal: true
 
 require_relative 'post_fetcher'
+require_relative 'strategy_factory'
 require_relative '../configuration'
 
 class PostSnapshotter
-  LOOKBACK = 2 * 24 * 60 * 60 # 2 days in seconds.
+  A_DAY = 24 * 60 * 60 # Seconds in a day.
+  private_constant :A_DAY
+
+  LOOKBACK = 2 * A_DAY
   private_constant :LOOKBACK
 
-  def initialize(storage_adapter:)
+  # post_fetcher is anything that responds to fetch like PostFetcher, e.g. a
+  # stub returning canned posts.
+  def initialize(storage_adapter:, post_fetcher: PostFetcher.new)
     @storage = storage_adapter
+    @post_fetcher = post_fetcher
   end
 
-  def snapshot(date:)
-    # 2x top n in case all the top n were sent yesterday.
-    posts = PostFetcher.fetch(top_k: 2 * Configuration::TOP_N_VALUES.max,
-                              points: Configuration::POINT_THRESHOLD_VALUES.min,
-                              since: date - LOOKBACK)
+  # Fetches enough posts for strategies, e.g. those with subscribers, as well
+  # as every configured strategy. See StrategyFactory.snapshot_limits.
+  def snapshot(date:, strategies: [], before: nil)
+    limits = StrategyFactory.snapshot_limits(
+      StrategyFactory.all_strategies + strategies
+    )
+    posts = @post_fetcher.fetch(
+      top_k: limits[:top_k],
+      points: limits[:points],
+      comments: Configuration::MIN_FETCH_COMMENTS,
+      tags: limits[:tags],
+      since: date - LOOKBACK,
+      before: before
+    )
 
     @storage.snapshot_posts(posts: posts, date: date)
 
     posts
   end
+
+  # Snapshots each day from from_date to to_date inclusive, e.g. to catch up
+  # after missed runs, skipping days that already have a snapshot. Only
+  # posts created before each date are included, though their points are
+  # as of now. Returns the snapshotted posts keyed by date.
+  def snapshot_range(from_date:, to_date:, strategies: [])
+    snapshots = {}
+    date = from_date
+    while date <= to_date
+      if @storage.snapshot?(date: date)
+        puts "Snapshot for #{date.strftime('%F')} already exists, skipping it"
+      else
+        snapshots[date] = snapshot(
+          date: date,
+          strategies: strategies,
+          before: date
+        )
+      end
+      date += A_DAY
+    end
+
+    snapshots
+  end
 end
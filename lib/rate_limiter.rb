@@ -0,0 +1,32 @@
+# frozen_string_literal: true
+
+require_relative 'errors'
+
+# Spaces out calls to at most requests_per_second. Callers block in acquire
+# until they are allowed to proceed.
+class RateLimiter
+  class InvalidRate < ArgumentError
+    include HNDigestError
+  end
+
+  def initialize(requests_per_second:)
+    unless requests_per_second.is_a?(Numeric) && requests_per_second.positive?
+      raise InvalidRate, 'requests_per_second must be > 0'
+    end
+
+    @interval = 1.0 / requests_per_second
+    @next_allowed_at = 0.0
+    @mutex = Mutex.new
+  end
+
+  def acquire
+    wait = @mutex.synchronize do
+      now = Process.clock_gettime(Process::CLOCK_MONOTONIC)
+      start_at = [now, @next_allowed_at].max
+      @next_allowed_at = start_at + @interval
+      start_at - now
+    end
+
+    sleep(wait) if wait.positive?
+  end
+end
@@ -2,15 +2,20 @@
 
 require 'aws-sdk-dynamodb'
 
+require_relative 'errors'
+require_relative '../configuration'
+
 class StorageAdapter
-  TABLE = 'HNDigest'
-  private_constant :TABLE
+  # DynamoDB kept returning unprocessed items for a batch request.
+  class BatchIncompleteError < StandardError
+    include HNDigestError
+  end
 
   SNAPSHOT_PARTITION_KEY = 'POSTS_SNAPSHOT'
   private_constant :SNAPSHOT_PARTITION_KEY
 
-  MODEL_TTL = 30 * 24 * 60 * 60 # Seconds in 30 days.
-  private_constant :MODEL_TTL
+  A_DAY = 24 * 60 * 60 # Seconds in a day.
+  private_constant :A_DAY
 
   DIGEST_PARTITION_KEY_PREFIX = 'DIGEST'
   private_constant :DIGEST_PARTITION_KEY_PREFIX
@@ -18,30 +23,70 @@ class StorageAdapter
   SUBSCRIBERS_PARTITION_KEY = 'SUBSCRIBERS'
   private_constant :SUBSCRIBERS_PARTITION_KEY
 
-  def initialize
+  BATCH_GET_LIMIT = 100 # Max keys per BatchGetItem call.
+  private_constant :BATCH_GET_LIMIT
+
+  BATCH_WRITE_LIMIT = 25 # Max requests per BatchWriteItem call.
+  private_constant :BATCH_WRITE_LIMIT
+
+  # Unprocessed items usually mean throttling, so each resubmission waits
+  # BATCH_RETRY_BASE_DELAY seconds, then twice that, and so on.
+  BATCH_MAX_ATTEMPTS = 8
+  private_constant :BATCH_MAX_ATTEMPTS
+
+  BATCH_RETRY_BASE_DELAY = 0.05
+  private_constant :BATCH_RETRY_BASE_DELAY
+
+  # Keeps each snapshot item well under DynamoDB's 400KB item limit.
+  SNAPSHOT_CHUNK_SIZE = 100
+  private_constant :SNAPSHOT_CHUNK_SIZE
+
+  # item_prefix namespaces every partition key as "<prefix>#<key>" so that
+  # several environments can share one table. Empty means no prefix.
+  # Snapshots and digests expire ttl_days after the date they are for.
+  def initialize(table: Configuration::DYNAMODB_TABLE,
+                 item_prefix: Configuration::ITEM_PREFIX,
+                 ttl_days: Configuration::DYNAMO_TTL_DAYS)
     @dynamodb = Aws::DynamoDB::Client.new
+    @table = table
+    @item_prefix = item_prefix
+    @ttl = ttl_days * A_DAY
   end
 
+  # Writes posts in chunks of SNAPSHOT_CHUNK_SIZE, with sort keys
+  # "<date>#0", "<date>#1" and so on, replacing any earlier snapshot for date.
   def snapshot_posts(posts:, date:)
     datestamp = datestamp(date)
-    item = {
-      PK: SNAPSHOT_PARTITION_KEY,
-      SK: datestamp,
-      posts: posts,
-      expires_at: date.to_i + MODEL_TTL
-    }
+    items = posts.each_slice(SNAPSHOT_CHUNK_SIZE).map.with_index do |chunk, i|
+      {
+        PK: partition_key(SNAPSHOT_PARTITION_KEY),
+        SK: "#{datestamp}##{i}",
+        posts: chunk.to_h,
+        expires_at: date.to_i + @ttl
+      }
+    end
+    written_keys = items.map { |item| item[:SK] }.to_set
+    stale_keys = snapshot_items(datestamp, projection_expression: 'PK, SK')
+                 .reject { |key| written_keys.include?(key['SK']) }
 
-    @dynamodb.put_item(table_name: TABLE, item: item)
+    put_requests = items.map { |item| { put_request: { item: item } } }
+    delete_requests = stale_keys.map { |key| { delete_request: { key: key } } }
+    batch_write_items(put_requests + delete_requests)
   end
 
+  def snapshot?(date:)
+    !snapshot_items(datestamp(date), projection_expression: 'SK').empty?
+  end
+
+  # Reassembles every chunk for date. Snapshots saved before chunking, as a
+  # single item with the bare date as its sort key, are read the same way.
   def fetch_post_snapshot(date:)
-    datestamp = datestamp(date)
-    item = fetch_item(
-      partition_key: SNAPSHOT_PARTITION_KEY,
-      sort_key: datestamp
-    )
+    items = snapshot_items(datestamp(date))
+    return nil if items.empty?
 
-    item && item['posts']
+    items.each_with_object({}) do |item, posts|
+      item['posts'].each { |id, post| posts[id] = integer_counts(post) }
+    end
   end
 
   def save_digest(type:, date:, posts:)
@@ -50,27 +95,97 @@ class StorageAdapter
       PK: digest_partition_key(type),
       SK: datestamp,
       posts: posts,
-      expires_at: date.to_i + MODEL_TTL
+      expires_at: date.to_i + @ttl
     }
 
-    @dynamodb.put_item(table_name: TABLE, item: item)
+    @dynamodb.put_item(table_name: @table, item: item)
   end
 
   def fetch_digest(type:, date:)
     datestamp = datestamp(date)
-    fetch_item(
+    item = fetch_item(
       partition_key: digest_partition_key(type),
       sort_key: datestamp
     )
+
+    item && digest_with_integer_counts(item)
+  end
+
+  # Records that a saved digest has gone out, as sent_at in epoch seconds, so
+  # a retried invocation doesn't send it again.
+  def mark_digest_sent(type:, date:, sent_at: Time.now)
+    @dynamodb.update_item(
+      table_name: @table,
+      key: { PK: digest_partition_key(type), SK: datestamp(date) },
+      update_expression: 'SET sent_at = :sent_at',
+      expression_attribute_values: { ':sent_at' => sent_at.to_i }
+    )
+  end
+
+  # Digests for several types on one date in as few reads as possible, keyed
+  # by type. Types with no saved digest are left out.
+  def fetch_digests(types:, date:)
+    datestamp = datestamp(date)
+    types_by_key = types.map { |type| [digest_partition_key(type), type] }.to_h
+
+    items = types_by_key.keys.each_slice(BATCH_GET_LIMIT).flat_map do |keys|
+      batch_get_items(keys.map { |key| { PK: key, SK: datestamp } })
+    end
+
+    items.map do |item|
+      [types_by_key.fetch(item['PK']), digest_with_integer_counts(item)]
+    end.to_h
   end
 
   def fetch_subscribers(type:)
     item = fetch_item(
-      partition_key: SUBSCRIBERS_PARTITION_KEY,
+      partition_key: partition_key(SUBSCRIBERS_PARTITION_KEY),
       sort_key: type
     )
 
-    item && item['emails']
+    item && normalize_emails(item['emails'])
+  end
+
+  # Digest types with at least one subscriber.
+  def fetch_active_subscription_types
+    fetch_subscriber_items
+      .reject { |item| item['emails'].nil? || item['emails'].empty? }
+      .map { |item| item['SK'] }
+      .to_set
+  end
+
+  # Number of subscribers for each digest type that has a subscriber item,
+  # keyed by type. Reads only the emails lists, one query for all types.
+  def count_subscribers_by_type
+    fetch_subscriber_items.map do |item|
+      [item['SK'], normalize_emails(item['emails']).size]
+    end.to_h
+  end
+
+  # Every subscription as { 'email' => ..., 'strategy' => <digest type> }.
+  def fetch_all_subscribers
+    each_subscriber.to_a
+  end
+
+  # Lazily yields the same hashes as fetch_all_subscribers, querying one page
+  # of up to page_size digest types at a time rather than loading them all.
+  def each_subscriber(page_size: nil)
+    subscriber_pages(page_size: page_size).lazy.flat_map do |page|
+      page.items.flat_map do |item|
+        normalize_emails(item['emails']).map do |email|
+          { 'email' => email, 'strategy' => item['SK'] }
+        end
+      end
+    end
+  end
+
+  # Stored emails that differ from their normalized form, e.g. mixed-case
+  # addresses saved before normalization. For diagnostics only.
+  def fetch_email_case_conflicts
+    fetch_subscriber_items
+      .flat_map { |item| item['emails'] || [] }
+      .reject { |email| normalize_email(email) == email }
+      .uniq
   end
 
   private
@@ -79,6 +194,22 @@ class StorageAdapter
     date.getutc.strftime('%F')
   end
 
+  # The SDK reads every DynamoDB number back as a BigDecimal, which would
+  # render as e.g. "0.153e3 points".
+  def integer_counts(post)
+    post.merge(
+      post.slice('points', 'num_comments', 'word_count')
+          .compact
+          .transform_values(&:to_i)
+    )
+  end
+
+  def digest_with_integer_counts(item)
+    return item if item['posts'].nil?
+
+    item.merge('posts' => item['posts'].map { |post| integer_counts(post) })
+  end
+
   def fetch_item(partition_key:, sort_key:)
     @dynamodb.get_item(
       {
@@ -86,12 +217,102 @@ class StorageAdapter
           PK: partition_key,
           SK: sort_key
         },
-        table_name: TABLE
+        table_name: @table
       }
     )&.item
   end
 
+  # Matches both "<date>#<chunk>" and the older bare "<date>" sort keys.
+  def snapshot_items(datestamp, projection_expression: nil)
+    @dynamodb.query(
+      {
+        table_name: @table,
+        key_condition_expression: 'PK = :pk AND begins_with(SK, :date)',
+        expression_attribute_values: {
+          ':pk' => partition_key(SNAPSHOT_PARTITION_KEY),
+          ':date' => datestamp
+        },
+        projection_expression: projection_expression
+      }.compact
+    ).flat_map(&:items)
+  end
+
+  def batch_write_items(requests)
+    requests.each_slice(BATCH_WRITE_LIMIT) do |slice|
+      with_batch_retries({ @table => slice }) do |request_items|
+        response = @dynamodb.batch_write_item(request_items: request_items)
+        response.unprocessed_items.transform_values do |reqs|
+          reqs.map(&:to_h)
+        end
+      end
+    end
+  end
+
+  def batch_get_items(keys)
+    items = []
+    with_batch_retries({ @table => { keys: keys } }) do |request_items|
+      response = @dynamodb.batch_get_item(request_items: request_items)
+      items.concat(response.responses.fetch(@table, []))
+      response.unprocessed_keys.transform_values(&:to_h)
+    end
+
+    items
+  end
+
+  # Yields request_items, then whatever the block returns as unprocessed,
+  # backing off between attempts until nothing is left.
+  def with_batch_retries(request_items)
+    attempt = 1
+    loop do
+      request_items = yield(request_items)
+      break if request_items.empty?
+
+      if attempt >= BATCH_MAX_ATTEMPTS
+        raise BatchIncompleteError,
+              "Batch request still had unprocessed items after #{attempt} " \
+              'attempts'
+      end
+
+      sleep(BATCH_RETRY_BASE_DELAY * 2**(attempt - 1))
+      attempt += 1
+    end
+  end
+
+  def fetch_subscriber_items
+    subscriber_pages.flat_map(&:items)
+  end
+
+  # Responses are pageable, so enumerating them issues follow-up queries
+  # from last_evaluated_key as needed.
+  def subscriber_pages(page_size: nil)
+    @dynamodb.query(
+      {
+        table_name: @table,
+        key_condition_expression: 'PK = :pk',
+        expression_attribute_values: {
+          ':pk' => partition_key(SUBSCRIBERS_PARTITION_KEY)
+        },
+        projection_expression: 'SK, emails',
+        limit: page_size
+      }.compact
+    )
+  end
+
+  def normalize_emails(emails)
+    (emails || []).map { |email| normalize_email(email) }.uniq
+  end
+
+  def normalize_email(email)
+    email.strip.downcase
+  end
+
   def digest_partition_key(type)
-    "#{DIGEST_PARTITION_KEY_PREFIX}##{type}"
+    partition_key("#{DIGEST_PARTITION_KEY_PREFIX}##{type}")
+  end
+
+  def partition_key(key)
+    return key if @item_prefix.empty?
+
+    "#{@item_prefix}##{key}"
   end
 end
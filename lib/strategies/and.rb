@@ -0,0 +1,50 @@
+# frozen_string_literal: true
+
+require 'set'
+
+require_relative 'validation_error'
+require_relative 'weekly'
+
+module Strategies
+  # Posts selected by both strategies, in the left strategy's order, e.g. the
+  # top 10 stories that also have 100+ points. Only built from stored types;
+  # StrategyFactory.all_strategies doesn't offer any.
+  class And
+    def initialize(left_strategy, right_strategy)
+      if [left_strategy, right_strategy].any? { |s| s.is_a?(Weekly) }
+        raise ValidationError,
+              "AND can't contain weekly strategies, wrap the AND in WEEKLY " \
+              'instead'
+      end
+
+      @left_strategy = left_strategy
+      @right_strategy = right_strategy
+    end
+
+    def type
+      "AND(#{@left_strategy.type},#{@right_strategy.type})"
+    end
+
+    def description
+      "#{@left_strategy.description}, also in " \
+        "#{@right_strategy.description.downcase}"
+    end
+
+    def snapshot_needs
+      @left_strategy.snapshot_needs + @right_strategy.snapshot_needs
+    end
+
+    def matches?(post)
+      @left_strategy.matches?(post) && @right_strategy.matches?(post)
+    end
+
+    def select(all_posts)
+      right_ids = @right_strategy.select(all_posts)
+                                 .map { |post| post['objectID'] }
+                                 .to_set
+      @left_strategy.select(all_posts).select do |post|
+        right_ids.include?(post['objectID'])
+      end
+    end
+  end
+end
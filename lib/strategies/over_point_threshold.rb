@@ -1,8 +1,20 @@
 # frozen_string_literal: true
 
+require_relative 'validation_error'
+
 module Strategies
   class OverPointThreshold
+    MAX_THRESHOLD = 10_000
+    private_constant :MAX_THRESHOLD
+
     def initialize(point_threshold)
+      unless point_threshold.is_a?(Integer) &&
+             point_threshold.between?(1, MAX_THRESHOLD)
+        raise ValidationError,
+              'POINT_THRESHOLD needs a threshold between 1 and ' \
+              "#{MAX_THRESHOLD}, got #{point_threshold.inspect}"
+      end
+
       @point_threshold = point_threshold
     end
 
@@ -10,8 +22,20 @@ module Strategies
       "POINT_THRESHOLD##{@point_threshold}"
     end
 
+    def description
+      "All stories with #{@point_threshold}+ points"
+    end
+
+    def snapshot_needs
+      [{ min_points: @point_threshold }]
+    end
+
+    def matches?(post)
+      (post['points'] || 0) >= @point_threshold
+    end
+
     def select(all_posts)
-      all_posts.select { |post| post['points'] >= @point_threshold }
+      all_posts.select { |post| matches?(post) }
     end
   end
 end
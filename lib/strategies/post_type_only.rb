@@ -0,0 +1,45 @@
+# frozen_string_literal: true
+
+require_relative '../post'
+require_relative 'validation_error'
+
+module Strategies
+  # Every post of one type, e.g. all Show HN posts, with no point cutoff.
+  class PostTypeOnly
+    DESCRIPTIONS = {
+      show_hn: 'All Show HN posts',
+      ask_hn: 'All Ask HN posts'
+    }.freeze
+    private_constant :DESCRIPTIONS
+
+    def initialize(post_type)
+      unless DESCRIPTIONS.key?(post_type)
+        raise ValidationError,
+              "Post type digests support #{DESCRIPTIONS.keys.join(', ')}, " \
+              "got #{post_type.inspect}"
+      end
+
+      @post_type = post_type
+    end
+
+    def type
+      @post_type.to_s.upcase
+    end
+
+    def description
+      DESCRIPTIONS.fetch(@post_type)
+    end
+
+    def snapshot_needs
+      [{ tag: @post_type.to_s }]
+    end
+
+    def matches?(post)
+      Post.post_type(post) == @post_type
+    end
+
+    def select(all_posts)
+      all_posts.select { |post| matches?(post) }
+    end
+  end
+end
@@ -0,0 +1,46 @@
+# frozen_string_literal: true
+
+require_relative '../post'
+require_relative 'validation_error'
+require_relative 'weekly'
+
+module Strategies
+  # Restricts another strategy to posts carrying an Algolia tag.
+  class RequireTag
+    def initialize(tag, base_strategy)
+      if tag.to_s.strip.empty?
+        raise ValidationError, "TAGGED needs a tag, got #{tag.inspect}"
+      end
+
+      if base_strategy.is_a?(Weekly)
+        raise ValidationError,
+              "TAGGED can't contain a weekly strategy, use WEEKLY|TAGGED#... " \
+              'instead'
+      end
+
+      @tag = tag
+      @base_strategy = base_strategy
+    end
+
+    def type
+      "TAGGED##{@tag}|#{@base_strategy.type}"
+    end
+
+    def description
+      "#{@base_strategy.description}, tagged #{@tag}"
+    end
+
+    def snapshot_needs
+      @base_strategy.snapshot_needs + [{ tag: @tag }]
+    end
+
+    def matches?(post)
+      Post.tags(post).include?(@tag) && @base_strategy.matches?(post)
+    end
+
+    def select(all_posts)
+      tagged_posts = all_posts.select { |post| Post.tags(post).include?(@tag) }
+      @base_strategy.select(tagged_posts)
+    end
+  end
+end
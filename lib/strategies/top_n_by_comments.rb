@@ -0,0 +1,43 @@
+# frozen_string_literal: true
+
+require_relative 'validation_error'
+
+module Strategies
+  class TopNByComments
+    MAX_POSTS = 500
+    private_constant :MAX_POSTS
+
+    def initialize(num_posts)
+      unless num_posts.is_a?(Integer) && num_posts.between?(1, MAX_POSTS)
+        raise ValidationError,
+              "TOP_N_COMMENTS needs between 1 and #{MAX_POSTS} posts, " \
+              "got #{num_posts.inspect}"
+      end
+
+      @n = num_posts
+    end
+
+    def type
+      "TOP_N_COMMENTS##{@n}"
+    end
+
+    def description
+      "Top #{@n} stories by comments"
+    end
+
+    # Heavily discussed posts are fetched regardless (MIN_FETCH_COMMENTS);
+    # the top N by points fill out the rest.
+    def snapshot_needs
+      [{ top_n: @n }]
+    end
+
+    # Every post qualifies; select only keeps the most discussed.
+    def matches?(_post)
+      true
+    end
+
+    def select(all_posts)
+      all_posts.sort_by { |post| -(post['num_comments'] || 0) }.first(@n)
+    end
+  end
+end
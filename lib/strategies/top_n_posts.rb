@@ -1,8 +1,19 @@
 # frozen_string_literal: true
 
+require_relative 'validation_error'
+
 module Strategies
   class TopNPosts
+    MAX_POSTS = 500
+    private_constant :MAX_POSTS
+
     def initialize(num_posts)
+      unless num_posts.is_a?(Integer) && num_posts.between?(1, MAX_POSTS)
+        raise ValidationError,
+              "TOP_N needs between 1 and #{MAX_POSTS} posts, " \
+              "got #{num_posts.inspect}"
+      end
+
       @n = num_posts
     end
 
@@ -10,6 +21,19 @@ module Strategies
       "TOP_N##{@n}"
     end
 
+    def description
+      "Top #{@n} stories by points"
+    end
+
+    def snapshot_needs
+      [{ top_n: @n }]
+    end
+
+    # Any post can make the top N; only its rank decides.
+    def matches?(_post)
+      true
+    end
+
     def select(all_posts)
       all_posts.first(@n)
     end
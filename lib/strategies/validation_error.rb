@@ -0,0 +1,9 @@
+# frozen_string_literal: true
+
+require_relative '../errors'
+
+module Strategies
+  class ValidationError < ArgumentError
+    include HNDigestError
+  end
+end
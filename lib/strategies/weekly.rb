@@ -0,0 +1,38 @@
+# frozen_string_literal: true
+
+require_relative 'validation_error'
+
+module Strategies
+  # Applies a base strategy once a week to the posts from the past seven
+  # daily snapshots. DigestBuilder gathers those posts; see
+  # DigestBuilder#build_digest.
+  class Weekly
+    def initialize(base_strategy)
+      if base_strategy.is_a?(Weekly)
+        raise ValidationError, "WEEKLY can't be nested in another WEEKLY"
+      end
+
+      @base_strategy = base_strategy
+    end
+
+    def type
+      "WEEKLY|#{@base_strategy.type}"
+    end
+
+    def description
+      "#{@base_strategy.description} this week"
+    end
+
+    def snapshot_needs
+      @base_strategy.snapshot_needs
+    end
+
+    def matches?(post)
+      @base_strategy.matches?(post)
+    end
+
+    def select(all_posts)
+      @base_strategy.select(all_posts)
+    end
+  end
+end
@@ -1,8 +1,14 @@
 # frozen_string_literal: true
 
 require_relative '../configuration'
+require_relative 'strategies/and'
 require_relative 'strategies/over_point_threshold'
+require_relative 'strategies/post_type_only'
+require_relative 'strategies/require_tag'
+require_relative 'strategies/top_n_by_comments'
 require_relative 'strategies/top_n_posts'
+require_relative 'strategies/validation_error'
+require_relative 'strategies/weekly'
 
 class StrategyFactory
   def self.all_strategies
@@ -15,6 +21,110 @@ class StrategyFactory
       strategies << Strategies::OverPointThreshold.new(threshold)
     end
 
+    Configuration::TOP_N_BY_COMMENTS_VALUES.each do |n|
+      strategies << Strategies::TopNByComments.new(n)
+    end
+
+    Configuration::REQUIRED_TAG_VALUES.each do |tag|
+      Configuration::TOP_N_VALUES.each do |n|
+        strategies << Strategies::RequireTag.new(
+          tag,
+          Strategies::TopNPosts.new(n)
+        )
+      end
+    end
+
+    Configuration::POST_TYPE_VALUES.each do |post_type|
+      strategies << Strategies::PostTypeOnly.new(post_type)
+    end
+
+    Configuration::TOP_N_VALUES.each do |n|
+      strategies << Strategies::Weekly.new(Strategies::TopNPosts.new(n))
+    end
+
     strategies
   end
+
+  # Builds the strategy for a stored digest type such as "POINT_THRESHOLD#75".
+  # Any value in a strategy's valid range is accepted, not just the configured
+  # ones. Raises Strategies::ValidationError for anything else.
+  def self.from_type(type)
+    case type
+    when /\AAND\((?<args>.+)\)\z/
+      left, right = split_and_args(Regexp.last_match(:args), type)
+      Strategies::And.new(from_type(left), from_type(right))
+    when /\AWEEKLY\|(?<base>.+)\z/
+      Strategies::Weekly.new(from_type(Regexp.last_match(:base)))
+    when /\ATAGGED#(?<tag>[^|]+)\|(?<base>.+)\z/
+      Strategies::RequireTag.new(
+        Regexp.last_match(:tag),
+        from_type(Regexp.last_match(:base))
+      )
+    when /\ATOP_N#(?<n>[1-9]\d*)\z/
+      Strategies::TopNPosts.new(Integer(Regexp.last_match(:n), 10))
+    when /\ATOP_N_COMMENTS#(?<n>[1-9]\d*)\z/
+      Strategies::TopNByComments.new(Integer(Regexp.last_match(:n), 10))
+    when /\A(?<post_type>SHOW_HN|ASK_HN)\z/
+      Strategies::PostTypeOnly.new(
+        Regexp.last_match(:post_type).downcase.to_sym
+      )
+    when /\APOINT_THRESHOLD#(?<points>[1-9]\d*)\z/
+      Strategies::OverPointThreshold.new(
+        Integer(Regexp.last_match(:points), 10)
+      )
+    else
+      raise Strategies::ValidationError, "Unknown digest type #{type.inspect}"
+    end
+  end
+
+  # Splits "<left>,<right>" at the comma that isn't inside a nested AND(...).
+  def self.split_and_args(args, type)
+    depth = 0
+    args.each_char.with_index do |char, i|
+      case char
+      when '(' then depth += 1
+      when ')' then depth -= 1
+      when ','
+        return [args[0...i], args[(i + 1)..]] if depth.zero?
+      end
+    end
+
+    raise Strategies::ValidationError,
+          "AND needs two comma-separated types, got #{type.inspect}"
+  end
+  private_class_method :split_and_args
+
+  # Strategies for the given stored types. A type that doesn't parse is
+  # logged and skipped so one bad subscription can't stop the whole run.
+  def self.from_types(types)
+    types.sort.filter_map do |type|
+      from_type(type)
+    rescue Strategies::ValidationError => e
+      puts "Skipping digest type #{type.inspect}: #{e.message}"
+      nil
+    end
+  end
+
+  # What the daily snapshot must fetch for every one of strategies to see all
+  # the posts it could select: enough top stories for the largest N, every
+  # story down to the lowest point threshold, and every post of each tag.
+  # Each strategy reports these as snapshot_needs, e.g. [{ top_n: 10 }].
+  def self.snapshot_limits(strategies)
+    needs = strategies.flat_map(&:snapshot_needs)
+    top_n = needs.filter_map { |need| need[:top_n] }.max
+    min_points = needs.filter_map { |need| need[:min_points] }.min
+    {
+      # 2x top n in case all the top n were sent yesterday.
+      top_k: 2 * (top_n || Configuration::TOP_N_VALUES.max),
+      points: min_points || Configuration::POINT_THRESHOLD_VALUES.min,
+      tags: needs.filter_map { |need| need[:tag] }.uniq.sort
+    }
+  end
+
+  # Weekly strategies are only built and sent on the weekly run day.
+  def self.runs_on?(strategy, date)
+    return true unless strategy.is_a?(Strategies::Weekly)
+
+    date.wday == Configuration::WEEKLY_DIGEST_WDAY
+  end
 end
@@ -0,0 +1,20 @@
+# frozen_string_literal: true
+
+require 'csv'
+
+module SubscriberExporters
+  class CsvExporter
+    HEADERS = %w[email strategy].freeze
+    private_constant :HEADERS
+
+    # Writes each row to io as its page of subscribers arrives, so the whole
+    # export is never held in memory.
+    def export(storage_adapter:, io:)
+      csv = CSV.new(io)
+      csv << HEADERS
+      storage_adapter.each_subscriber.each do |subscriber|
+        csv << subscriber.values_at(*HEADERS)
+      end
+    end
+  end
+end
@@ -0,0 +1,11 @@
+# frozen_string_literal: true
+
+require 'json'
+
+module SubscriberExporters
+  class JsonExporter
+    def export(storage_adapter:, io:)
+      io.puts JSON.pretty_generate(storage_adapter.fetch_all_subscribers)
+    end
+  end
+end
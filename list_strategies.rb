@@ -0,0 +1,15 @@
+# frozen_string_literal: true
+
+require 'json'
+
+require_relative 'lib/strategy_factory'
+
+# Usage: ruby list_strategies.rb
+#
+# Prints every digest type that can be subscribed to as a JSON array of
+# { "id": ..., "description": ... }, e.g. for the subscribe form.
+strategies = StrategyFactory.all_strategies.map do |strategy|
+  { 'id' => strategy.type, 'description' => strategy.description }
+end
+
+puts JSON.pretty_generate(strategies)
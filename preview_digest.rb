@@ -0,0 +1,45 @@
+# frozen_string_literal: true
+
+require_relative 'configuration'
+require_relative 'lib/digest_builder'
+require_relative 'lib/digest_renderer'
+require_relative 'lib/storage_adapter'
+require_relative 'lib/strategy_factory'
+
+# Usage: ruby preview_digest.rb <digest type> <YYYY-MM-DD>
+#
+# Prints the HTML of the digest that would be built for the given type from
+# that day's snapshot. Nothing is saved and no mail is sent.
+type, day = ARGV
+unless type && day =~ /\A\d{4}-\d{2}-\d{2}\z/
+  abort 'Usage: ruby preview_digest.rb <digest type> <YYYY-MM-DD>'
+end
+
+strategy = begin
+  StrategyFactory.from_type(type)
+rescue Strategies::ValidationError => e
+  abort e.message
+end
+
+year, month, mday = day.split('-').map { |part| Integer(part, 10) }
+date = Time.gm(year, month, mday, Configuration::SNAPSHOT_DAILY_HOUR)
+
+storage_adapter = StorageAdapter.new
+snapshot = storage_adapter.fetch_post_snapshot(date: date)
+abort "No snapshot for #{day}" if snapshot.nil?
+
+digest_builder = DigestBuilder.new(
+  storage_adapter: storage_adapter,
+  max_posts_per_domain: Configuration::MAX_POSTS_PER_DOMAIN,
+  blocked_keywords: Configuration::DIGEST_BLOCKED_KEYWORDS,
+  lookback_days: Configuration::DIGEST_LOOKBACK_DAYS
+)
+posts = digest_builder.build_digest_dry_run(
+  digest_strategy: strategy,
+  date: date,
+  posts: snapshot.values
+)
+
+renderer = DigestRenderer.new(posts: posts, date: date, strategy: strategy)
+puts renderer.subject
+puts renderer.content
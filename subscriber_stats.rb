@@ -0,0 +1,17 @@
+# frozen_string_literal: true
+
+require 'json'
+
+require_relative 'lib/storage_adapter'
+require_relative 'lib/strategy_factory'
+
+# Usage: ruby subscriber_stats.rb
+#
+# Prints the number of subscribers for every digest type as a JSON object.
+# Types nobody subscribes to are shown as 0, and stored types that are no
+# longer offered are still included.
+counts = StrategyFactory.all_strategies.map { |strategy| [strategy.type, 0] }
+                        .to_h
+                        .merge(StorageAdapter.new.count_subscribers_by_type)
+
+puts JSON.pretty_generate(counts)
@@ -1,18 +1,25 @@
 # frozen_string_literal: true
 
+require 'aws-sdk-ses'
+
+require_relative 'configuration'
 require_relative 'lib/digest_mailer'
 require_relative 'lib/digest_renderer'
 require_relative 'lib/storage_adapter'
+require_relative 'lib/strategies/top_n_posts'
 
 date = Time.gm(2020, 5, 2)
+strategy = Strategies::TopNPosts.new(10)
 
 sa = StorageAdapter.new
-digest = sa.fetch_digest(type: 'TOP_N#10', date: date)
+digest = sa.fetch_digest(type: strategy.type, date: date)
 posts = digest['posts']
 
-renderer = DigestRenderer.new(posts: posts, date: date)
+renderer = DigestRenderer.new(posts: posts, date: date, strategy: strategy)
 
-mailer = DigestMailer.new(api_key: ENV['SENDGRID_API_KEY'])
+mailer = DigestMailer.new(
+  ses_client: Aws::SES::Client.new(region: Configuration::SES_REGION)
+)
 mailer.send_mail(
   renderer: renderer,
   recipients: ['test1@samshadwell.com', 'test2@samshadwell.com']